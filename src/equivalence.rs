@@ -0,0 +1,425 @@
+//! Verification helpers for trusting a new `MinHeap` backend as a drop-in
+//! replacement for an existing one. The same transaction sequence is replayed
+//! against a warehouse built on each backend and every observable outcome is
+//! compared.
+
+use crate::inventory_heap::{Inventory, InventoryView, MinHeap};
+use crate::{Transaction, Warehouse};
+use rust_decimal::Decimal;
+
+/// Replays `transactions` against a warehouse backed by `A` and one backed by
+/// `B`, panicking on the first divergence. After every transaction both
+/// warehouses must have accepted or rejected it alike, recorded the same
+/// receipt and agree on the price and quantity of the cheapest block of every
+/// product. The latter is the view the next consume of that product yields,
+/// so backends that split equal-price blocks differently are caught. Once the
+/// sequence is exhausted both are drained, which must yield the same stock
+/// level and valuation per product.
+///
+/// `Inventory` compares by price alone, so blocks are compared as
+/// `(price_per_item, quantity)` pairs throughout.
+pub fn assert_equivalent<A: MinHeap, B: MinHeap>(transactions: &[Transaction]) {
+    let mut a: Warehouse<A> = Warehouse::default();
+    let mut b: Warehouse<B> = Warehouse::default();
+
+    for (index, t) in transactions.iter().enumerate() {
+        let a_accepted = a.transact(t.clone()).is_ok();
+        let b_accepted = b.transact(t.clone()).is_ok();
+        assert_eq!(
+            a_accepted, b_accepted,
+            "backends disagree on whether transaction {} is valid",
+            index
+        );
+
+        if a_accepted {
+            assert_eq!(
+                last_receipt(&a),
+                last_receipt(&b),
+                "backends disagree on the receipt for transaction {}",
+                index
+            );
+        }
+
+        assert_eq!(
+            min_blocks(&a),
+            min_blocks(&b),
+            "backends disagree on the cheapest blocks after transaction {}",
+            index
+        );
+    }
+
+    let mut b_drained = drain(&mut b);
+    for (product, a_prices) in drain(&mut a) {
+        let b_prices = b_drained.remove(&product).unwrap_or_default();
+        assert_eq!(
+            a_prices.len(),
+            b_prices.len(),
+            "backends disagree on the stock level of '{}'",
+            product
+        );
+        assert_eq!(
+            a_prices.iter().sum::<Decimal>(),
+            b_prices.iter().sum::<Decimal>(),
+            "backends disagree on the valuation of '{}'",
+            product
+        );
+        assert_eq!(a_prices, b_prices, "backends disagree on '{}'", product);
+    }
+
+    assert!(
+        b_drained.is_empty(),
+        "only the second backend holds stock of {:?}",
+        b_drained.keys().collect::<Vec<_>>()
+    );
+}
+
+/// The blocks created or consumed by the most recently accepted transaction.
+fn last_receipt<T: MinHeap>(warehouse: &Warehouse<T>) -> Vec<BlockKey> {
+    match warehouse.transaction_history.history.last() {
+        Some(entry) => entry.blocks.iter().map(block_key).collect(),
+        None => vec![],
+    }
+}
+
+/// The cheapest block of every known product, ordered by product name. Empty
+/// products map to `None`.
+fn min_blocks<T: MinHeap>(warehouse: &Warehouse<T>) -> Vec<(String, Option<Vec<BlockKey>>)> {
+    let mut blocks: Vec<(String, Option<Vec<BlockKey>>)> = warehouse
+        .inventory_id_map
+        .product_strings_to_ids
+        .iter()
+        .map(|(product, id)| {
+            let view = warehouse
+                .inventory_heaps
+                .get(id)
+                // size rather than is_empty, so a backend that misreports
+                // being empty is caught by what the warehouse accepts.
+                .filter(|heap| heap.size() > 0)
+                .map(|heap| heap.get_min().inventory.iter().map(block_key).collect());
+            (product.clone(), view)
+        })
+        .collect();
+    blocks.sort();
+    blocks
+}
+
+/// A block as `(price_per_item, quantity)`.
+type BlockKey = (Decimal, usize);
+
+fn block_key(inventory: &Inventory) -> BlockKey {
+    (inventory.price_per_item, inventory.quantity)
+}
+
+/// Extracts every remaining item from the warehouse, returning the price paid
+/// for each one in extraction order, keyed by product name.
+fn drain<T: MinHeap>(
+    warehouse: &mut Warehouse<T>,
+) -> std::collections::HashMap<String, Vec<Decimal>> {
+    let mut drained = std::collections::HashMap::new();
+
+    for (product, id) in &warehouse.inventory_id_map.product_strings_to_ids {
+        let mut prices = vec![];
+        if let Some(heap) = warehouse.inventory_heaps.get_mut(id) {
+            while !heap.is_empty() {
+                prices.push(heap.extract().inventory[0].price_per_item);
+            }
+        }
+        drained.insert(product.clone(), prices);
+    }
+
+    drained
+}
+
+/// A deliberately naive backend that keeps its blocks in a sorted vector. It
+/// serves as the reference implementation the real backends are checked
+/// against.
+#[derive(Default)]
+pub struct SortedInventory {
+    inventory: Vec<Inventory>,
+}
+
+impl MinHeap for SortedInventory {
+    fn heapify(&mut self, _index: usize) {
+        self.inventory.sort();
+    }
+
+    fn insert(&mut self, inventory: Inventory) {
+        self.inventory.push(inventory);
+        self.heapify(0);
+    }
+
+    fn delete(&mut self) {
+        if self.inventory.is_empty() {
+            return;
+        }
+
+        if self.inventory[0].quantity > 1 {
+            self.inventory[0].quantity -= 1;
+            return;
+        }
+
+        self.inventory.remove(0);
+    }
+
+    fn extract(&mut self) -> InventoryView {
+        let min = self.get_min();
+        self.delete();
+        min
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inventory.is_empty()
+    }
+
+    fn size(&self) -> usize {
+        self.inventory.len()
+    }
+
+    fn get_min(&self) -> InventoryView {
+        InventoryView {
+            inventory: vec![self.inventory[0]],
+        }
+    }
+
+    fn new() -> Self {
+        SortedInventory { inventory: vec![] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::equivalence::{assert_equivalent, SortedInventory};
+    use crate::inventory_heap::{Inventory, InventoryHeap, InventoryView, MinHeap};
+    use crate::{create_transaction, Transaction, TransactionType};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use rust_decimal::Decimal;
+
+    /// Seeded so that a failing sequence can be replayed exactly. Prices per
+    /// item are unique, as backends are free to break ties between
+    /// equal-price blocks differently and would rightly be told apart.
+    fn random_transactions(seed: u64, count: usize) -> Vec<Transaction> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let products = ["Acrylic Box", "Wooden Crate", "Steel Drum"];
+
+        (0..count)
+            .map(|index| {
+                let product = String::from(products[rng.gen_range(0..products.len())]);
+                if rng.gen_bool(0.5) {
+                    let quantity = rng.gen_range(1..10);
+                    let price_per_item =
+                        Decimal::new(rng.gen_range(1..1000) * 1000 + index as i64, 3);
+                    let total_cost = price_per_item * Decimal::from(quantity);
+                    create_transaction(
+                        product,
+                        Some(total_cost),
+                        TransactionType::Produce,
+                        quantity,
+                    )
+                } else {
+                    create_transaction(product, None, TransactionType::Consume, 1)
+                }
+            })
+            .collect()
+    }
+
+    /// Hands out the most expensive block first, so it must be caught.
+    struct MaxFirstInventory(SortedInventory);
+
+    impl MinHeap for MaxFirstInventory {
+        fn heapify(&mut self, index: usize) {
+            self.0.heapify(index);
+            self.0.inventory.reverse();
+        }
+
+        fn insert(&mut self, inventory: Inventory) {
+            self.0.inventory.push(inventory);
+            self.heapify(0);
+        }
+
+        fn delete(&mut self) {
+            self.0.delete();
+        }
+
+        fn extract(&mut self) -> InventoryView {
+            self.0.extract()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn get_min(&self) -> InventoryView {
+            self.0.get_min()
+        }
+
+        fn new() -> Self {
+            MaxFirstInventory(SortedInventory::new())
+        }
+    }
+
+    /// Breaks ties between equal-price blocks newest first, where the
+    /// reference takes the oldest first. Prices paid always agree, so only
+    /// the block quantities give it away.
+    struct NewestTiedFirstInventory(SortedInventory);
+
+    impl MinHeap for NewestTiedFirstInventory {
+        fn heapify(&mut self, index: usize) {
+            self.0.heapify(index);
+        }
+
+        fn insert(&mut self, inventory: Inventory) {
+            self.0.inventory.insert(0, inventory);
+            self.heapify(0);
+        }
+
+        fn delete(&mut self) {
+            self.0.delete();
+        }
+
+        fn extract(&mut self) -> InventoryView {
+            self.0.extract()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn get_min(&self) -> InventoryView {
+            self.0.get_min()
+        }
+
+        fn new() -> Self {
+            NewestTiedFirstInventory(SortedInventory::new())
+        }
+    }
+
+    /// Claims to never run out, so the warehouse accepts consumes that the
+    /// reference rejects.
+    struct NeverEmptyInventory(SortedInventory);
+
+    impl MinHeap for NeverEmptyInventory {
+        fn heapify(&mut self, index: usize) {
+            self.0.heapify(index);
+        }
+
+        fn insert(&mut self, inventory: Inventory) {
+            self.0.insert(inventory);
+        }
+
+        fn delete(&mut self) {
+            self.0.delete();
+        }
+
+        fn extract(&mut self) -> InventoryView {
+            let min = self.get_min();
+            self.delete();
+            min
+        }
+
+        fn is_empty(&self) -> bool {
+            false
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn get_min(&self) -> InventoryView {
+            if self.0.is_empty() {
+                return InventoryView {
+                    inventory: vec![Inventory::default()],
+                };
+            }
+            self.0.get_min()
+        }
+
+        fn new() -> Self {
+            NeverEmptyInventory(SortedInventory::new())
+        }
+    }
+
+    #[test]
+    fn test_inventory_heap_matches_reference() {
+        for seed in 0..20 {
+            assert_equivalent::<InventoryHeap, SortedInventory>(&random_transactions(seed, 100));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "is valid")]
+    fn test_diverging_acceptance_should_panic() {
+        let transactions = vec![
+            create_transaction(
+                String::from("Acrylic Box"),
+                Some(Decimal::new(1, 0)),
+                TransactionType::Produce,
+                1,
+            ),
+            create_transaction(
+                String::from("Acrylic Box"),
+                None,
+                TransactionType::Consume,
+                1,
+            ),
+            create_transaction(
+                String::from("Acrylic Box"),
+                None,
+                TransactionType::Consume,
+                1,
+            ),
+        ];
+
+        assert_equivalent::<InventoryHeap, NeverEmptyInventory>(&transactions);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_diverging_backend_should_panic() {
+        let transactions = vec![
+            create_transaction(
+                String::from("Acrylic Box"),
+                Some(Decimal::new(1, 0)),
+                TransactionType::Produce,
+                1,
+            ),
+            create_transaction(
+                String::from("Acrylic Box"),
+                Some(Decimal::new(2, 0)),
+                TransactionType::Produce,
+                1,
+            ),
+        ];
+
+        assert_equivalent::<InventoryHeap, MaxFirstInventory>(&transactions);
+    }
+
+    #[test]
+    #[should_panic(expected = "cheapest blocks")]
+    fn test_differently_split_equal_price_blocks_should_panic() {
+        let transactions = vec![
+            create_transaction(
+                String::from("Acrylic Box"),
+                Some(Decimal::new(2, 0)),
+                TransactionType::Produce,
+                2,
+            ),
+            create_transaction(
+                String::from("Acrylic Box"),
+                Some(Decimal::new(5, 0)),
+                TransactionType::Produce,
+                5,
+            ),
+        ];
+
+        assert_equivalent::<SortedInventory, NewestTiedFirstInventory>(&transactions);
+    }
+}
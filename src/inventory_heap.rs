@@ -1,5 +1,6 @@
 use rust_decimal::prelude::*;
 use std::cmp::Ordering;
+use std::time::SystemTime;
 
 #[derive(Default, Clone, Copy, Eq)]
 pub struct Inventory {
@@ -30,6 +31,24 @@ pub trait MinHeap {
     fn get_min(&self) -> InventoryView;
 
     fn new() -> Self;
+
+    /// Inserts a block received at `received`. Heaps that order purely by
+    /// price ignore the time.
+    fn insert_at(&mut self, inventory: Inventory, _received: SystemTime) {
+        self.insert(inventory);
+    }
+
+    /// Returns the block that would be extracted at `now`. Heaps that order
+    /// purely by price ignore the time.
+    fn get_min_at(&self, _now: SystemTime) -> InventoryView {
+        self.get_min()
+    }
+
+    /// Extracts a single item as of `now`. Heaps that order purely by price
+    /// ignore the time.
+    fn extract_at(&mut self, _now: SystemTime) -> InventoryView {
+        self.extract()
+    }
 }
 
 /// Heavily influenced by
@@ -59,9 +78,7 @@ impl MinHeap for InventoryHeap {
         }
 
         if smallest != index {
-            let tmp = self.heap[index];
-            self.heap[index] = self.heap[smallest];
-            self.heap[smallest] = tmp;
+            self.heap.swap(index, smallest);
             self.heapify(smallest);
         }
     }
@@ -77,9 +94,7 @@ impl MinHeap for InventoryHeap {
                 return;
             }
 
-            let tmp = self.heap[parent_index];
-            self.heap[parent_index] = self.heap[index];
-            self.heap[index] = tmp;
+            self.heap.swap(parent_index, index);
 
             index = parent_index;
         }
@@ -87,7 +102,7 @@ impl MinHeap for InventoryHeap {
 
     fn delete(&mut self) {
         // Nothing to do, heap is empty. Results in a no-op.
-        if self.heap.len() == 0 {
+        if self.heap.is_empty() {
             return;
         }
 
@@ -105,7 +120,7 @@ impl MinHeap for InventoryHeap {
     fn extract(&mut self) -> InventoryView {
         let min = self.get_min();
         self.delete();
-        return min;
+        min
     }
 
     fn is_empty(&self) -> bool {
@@ -121,7 +136,7 @@ impl MinHeap for InventoryHeap {
 
         InventoryView {
             inventory: vec![Inventory {
-                price_per_item: inventory.price_per_item.clone(),
+                price_per_item: inventory.price_per_item,
                 quantity: inventory.quantity,
             }],
         }
@@ -193,7 +208,10 @@ mod tests {
             quantity: 10,
         };
         heap.insert(inv);
-        assert_eq!(heap.get_min().price_per_item, inv.price_per_item)
+        assert_eq!(
+            heap.get_min().inventory[0].price_per_item,
+            inv.price_per_item
+        )
     }
 
     #[test]
@@ -211,7 +229,7 @@ mod tests {
 
         let min = heap.get_min();
 
-        assert_eq!(min.price_per_item, dec!(1));
+        assert_eq!(min.inventory[0].price_per_item, dec!(1));
     }
 
     #[test]
@@ -284,9 +302,9 @@ mod tests {
             heap.insert(inv);
         }
 
-        let mut smallest = heap.extract()[0].price_per_item;
+        let mut smallest = heap.extract().inventory[0].price_per_item;
         while !heap.is_empty() {
-            let heap_min = heap.extract()[0].price_per_item;
+            let heap_min = heap.extract().inventory[0].price_per_item;
             assert!(smallest <= heap_min);
             smallest = heap_min;
         }
@@ -1,53 +1,170 @@
 use crate::inventory_heap::{Inventory, InventoryHeap, MinHeap};
+use crate::scored_heap::{CheapestUnlessStale, ScoredHeap};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::Formatter;
+use std::time::{Duration, SystemTime};
 
+#[cfg(test)]
+mod equivalence;
 mod inventory_heap;
+mod scored_heap;
 
-#[derive(Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 enum TransactionType {
     Produce,
     Consume,
+    Archive,
+    Restore,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum HashAlgorithm {
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Length of a digest produced by this algorithm, in hex characters.
+    fn digest_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 64,
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+/// Points at evidence kept outside the warehouse, such as an invoice PDF or
+/// a photo of damaged goods. Content hashes carry the algorithm that
+/// produced them so the evidence can be verified against them later.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum DocumentReference {
+    Url(String),
+    ContentHash {
+        algorithm: HashAlgorithm,
+        digest: String,
+    },
+}
+
+const URL_SCHEMES: [&str; 3] = ["http://", "https://", "file://"];
+
+impl DocumentReference {
+    /// Urls must use one of `URL_SCHEMES` and name something after it, with
+    /// no whitespace. Content hashes must be lowercase hex of the
+    /// algorithm's digest length.
+    fn is_valid(&self) -> bool {
+        match self {
+            DocumentReference::Url(url) => {
+                !url.chars().any(char::is_whitespace)
+                    && URL_SCHEMES.iter().any(|scheme| {
+                        url.strip_prefix(scheme)
+                            .is_some_and(|rest| !rest.is_empty())
+                    })
+            }
+            DocumentReference::ContentHash { algorithm, digest } => {
+                digest.len() == algorithm.digest_len()
+                    && digest
+                        .chars()
+                        .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+            }
+        }
+    }
+}
+
+impl fmt::Display for DocumentReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentReference::Url(url) => write!(f, "url({})", url),
+            DocumentReference::ContentHash { algorithm, digest } => {
+                write!(f, "hash({}:{})", algorithm, digest)
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
 struct Transaction {
     transaction_type: TransactionType,
     inventory_id: String,
     quantity: usize,
     total_cost: Option<Decimal>,
+    documents: Vec<DocumentReference>,
+    timestamp: SystemTime,
+}
+
+impl Transaction {
+    /// Dates the transaction, e.g. to record goods received in the past.
+    /// Transactions are dated when they are created otherwise.
+    fn at(mut self, timestamp: SystemTime) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Attaches a reference to an external document. The reference is kept
+    /// in the transaction history alongside the transaction itself.
+    fn with_document(mut self, document: DocumentReference) -> Self {
+        self.documents.push(document);
+        self
+    }
+}
+
+/// A processed transaction together with the inventory blocks it created
+/// (produce) or consumed (consume).
+struct HistoryEntry {
+    transaction: Transaction,
+    blocks: Vec<Inventory>,
 }
 
+/// Transactions are identified by their position in the history.
 #[derive(Default)]
 struct TransactionHistory {
-    history: Vec<Transaction>,
+    history: Vec<HistoryEntry>,
 }
 
 #[derive(Default)]
 struct InventoryIdMap {
     product_strings_to_ids: HashMap<String, u64>,
+    archived_ids: HashSet<u64>,
     next_id: u64,
 }
 
 impl InventoryIdMap {
-    fn get_inventory_key(&mut self, inventory: &String) -> Result<u64, WarehouseError> {
+    fn get_inventory_key(&mut self, inventory: &str) -> Result<u64, WarehouseError> {
         if !self.product_strings_to_ids.contains_key(inventory) {
             self.insert_new_key(inventory);
         }
 
         match self.product_strings_to_ids.get(inventory) {
-            Some(key) => Ok(key.clone()),
+            Some(key) => Ok(*key),
             None => Err(WarehouseError),
         }
     }
 
-    fn insert_new_key(&mut self, inventory: &String) {
+    fn insert_new_key(&mut self, inventory: &str) {
         self.product_strings_to_ids
-            .insert(inventory.clone(), self.next_id);
+            .insert(inventory.to_string(), self.next_id);
         self.next_id += 1;
     }
+
+    /// Looks up an existing product without registering it.
+    fn find_inventory_key(&self, inventory: &str) -> Option<u64> {
+        self.product_strings_to_ids.get(inventory).copied()
+    }
+
+    fn is_archived(&self, inventory: &str) -> bool {
+        match self.find_inventory_key(inventory) {
+            Some(key) => self.archived_ids.contains(&key),
+            None => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -62,7 +179,6 @@ impl fmt::Display for WarehouseError {
     }
 }
 
-#[derive(Default)]
 struct Warehouse<T>
 where
     T: MinHeap,
@@ -72,41 +188,74 @@ where
     transaction_history: TransactionHistory,
 }
 
+// Not derived, as that would require every heap type to be Default too.
+impl<T: MinHeap> Default for Warehouse<T> {
+    fn default() -> Self {
+        Warehouse {
+            inventory_id_map: InventoryIdMap::default(),
+            inventory_heaps: HashMap::new(),
+            transaction_history: TransactionHistory::default(),
+        }
+    }
+}
+
 impl<T: MinHeap> Warehouse<T> {
     fn transact(&mut self, t: Transaction) -> Result<(), WarehouseError> {
         self.validate_transaction(&t)?;
 
-        match t.transaction_type {
-            TransactionType::Produce => {
-                self.produce(&t)?;
-            }
-            TransactionType::Consume => {
-                self.consume(&t)?;
-            }
+        let blocks = match t.transaction_type {
+            TransactionType::Produce => vec![self.produce(&t)?],
+            TransactionType::Consume => self.consume(&t)?,
+            TransactionType::Archive => self.set_archived(&t, true)?,
+            TransactionType::Restore => self.set_archived(&t, false)?,
+        };
+
+        for document in &t.documents {
+            println!("Referenced document {}", document);
         }
 
-        self.transaction_history.history.push(t);
+        self.transaction_history.history.push(HistoryEntry {
+            transaction: t,
+            blocks,
+        });
 
         Ok(())
     }
 
     fn validate_transaction(&self, t: &Transaction) -> Result<(), WarehouseError> {
-        if t.transaction_type == TransactionType::Produce {
-            if t.total_cost.is_none() {
-                println!("cost_total should be not be None if TransactionType is Produce");
-                return Err(WarehouseError);
-            }
-        } else if t.transaction_type == TransactionType::Consume {
-            if t.total_cost.is_some() {
-                println!("cost_total should be not be Some(_) if TransactionType is Consume");
-                return Err(WarehouseError);
-            }
+        if t.transaction_type == TransactionType::Produce && t.total_cost.is_none() {
+            println!("cost_total should be not be None if TransactionType is Produce");
+            return Err(WarehouseError);
+        } else if t.transaction_type != TransactionType::Produce && t.total_cost.is_some() {
+            println!("cost_total should be not be Some(_) unless TransactionType is Produce");
+            return Err(WarehouseError);
+        }
+
+        // The price per item is the total cost divided by the quantity.
+        if t.transaction_type == TransactionType::Produce && t.quantity == 0 {
+            println!("quantity should not be 0 if TransactionType is Produce");
+            return Err(WarehouseError);
+        }
+
+        if let Some(document) = t.documents.iter().find(|document| !document.is_valid()) {
+            println!("Referenced document {} is not a valid reference", document);
+            return Err(WarehouseError);
+        }
+
+        if t.transaction_type == TransactionType::Produce
+            && self.inventory_id_map.is_archived(&t.inventory_id)
+        {
+            println!(
+                "Trying to produce inventory({}) that has been archived",
+                t.inventory_id
+            );
+            return Err(WarehouseError);
         }
 
         Ok(())
     }
 
-    fn produce(&mut self, t: &Transaction) -> Result<(), WarehouseError> {
+    fn produce(&mut self, t: &Transaction) -> Result<Inventory, WarehouseError> {
         let id = self.inventory_id_map.get_inventory_key(&t.inventory_id)?;
 
         let inventory = Inventory {
@@ -114,27 +263,23 @@ impl<T: MinHeap> Warehouse<T> {
             quantity: t.quantity,
         };
 
-        self.inventory_heaps
-            .entry(id)
-            .and_modify(|heap| heap.insert(inventory))
-            .or_insert_with(|| {
-                let mut heap = T::new();
-                heap.insert(inventory);
-                heap
-            });
+        let heap = self.inventory_heaps.entry(id).or_insert_with(T::new);
+        heap.insert_at(inventory, t.timestamp);
 
         println!("Processed a produce transaction for product '{}' with quantity {} and price per item {}",
                  t.inventory_id, inventory.quantity, inventory.price_per_item);
 
-        Ok(())
+        Ok(inventory)
     }
 
-    fn consume(&mut self, t: &Transaction) -> Result<(), WarehouseError> {
-        let id = self.inventory_id_map.get_inventory_key(&t.inventory_id)?;
+    fn consume(&mut self, t: &Transaction) -> Result<Vec<Inventory>, WarehouseError> {
+        // Looked up rather than registered, so a consume of an unknown
+        // product doesn't leave it behind in the product listings.
+        let id = self.inventory_id_map.find_inventory_key(&t.inventory_id);
 
-        let inventory_view = match self.inventory_heaps.get_mut(&id) {
-            Some(heap) => Ok(heap.extract()),
-            None => {
+        let inventory_view = match id.and_then(|id| self.inventory_heaps.get_mut(&id)) {
+            Some(heap) if !heap.is_empty() => Ok(heap.extract_at(t.timestamp)),
+            _ => {
                 println!(
                     "Trying to consume inventory({}) that doesn't exist",
                     t.inventory_id
@@ -147,48 +292,760 @@ impl<T: MinHeap> Warehouse<T> {
             "Processed a consume transaction for product '{}'",
             t.inventory_id
         );
-        for inventory_block in inventory_view.inventory {
+        // The view reports the whole block, but extract only takes a single
+        // item from it.
+        let consumed: Vec<Inventory> = inventory_view
+            .inventory
+            .iter()
+            .map(|inventory_block| Inventory {
+                price_per_item: inventory_block.price_per_item,
+                quantity: 1,
+            })
+            .collect();
+        for inventory_block in &consumed {
             println!(
                 "Consumed quantity ({}) at price ({})",
                 inventory_block.quantity, inventory_block.price_per_item
             );
         }
 
-        Ok(())
+        Ok(consumed)
+    }
+
+    /// Archives or restores a product. Neither touches its stock, so no
+    /// blocks are returned.
+    fn set_archived(
+        &mut self,
+        t: &Transaction,
+        archived: bool,
+    ) -> Result<Vec<Inventory>, WarehouseError> {
+        let id = match self.inventory_id_map.find_inventory_key(&t.inventory_id) {
+            Some(id) => id,
+            None => {
+                println!(
+                    "Trying to archive or restore inventory({}) that doesn't exist",
+                    t.inventory_id
+                );
+                return Err(WarehouseError);
+            }
+        };
+
+        if archived {
+            if !self.inventory_id_map.archived_ids.insert(id) {
+                println!("Inventory({}) is already archived", t.inventory_id);
+                return Err(WarehouseError);
+            }
+            println!("Archived product '{}'", t.inventory_id);
+        } else {
+            if !self.inventory_id_map.archived_ids.remove(&id) {
+                println!("Inventory({}) is not archived", t.inventory_id);
+                return Err(WarehouseError);
+            }
+            println!("Restored product '{}'", t.inventory_id);
+        }
+
+        Ok(vec![])
+    }
+
+    /// Reconstructs what a transaction in this warehouse's history did, as
+    /// identified by its position there: the blocks it created or
+    /// consumed and at what prices, its cost of goods if it was a consume,
+    /// and the stock level of the product once it was processed. There are
+    /// no reports yet, so it can't say which reports the transaction feeds
+    /// into.
+    fn explain(&self, transaction_id: usize) -> Result<String, WarehouseError> {
+        let history = &self.transaction_history.history;
+        let entry = match history.get(transaction_id) {
+            Some(entry) => entry,
+            None => {
+                println!(
+                    "Trying to explain transaction({}) that doesn't exist",
+                    transaction_id
+                );
+                return Err(WarehouseError);
+            }
+        };
+        let t = &entry.transaction;
+
+        let mut lines = vec![];
+        match t.transaction_type {
+            TransactionType::Produce => {
+                lines.push(format!(
+                    "Transaction {} produced {} of '{}' for a total cost of {}",
+                    transaction_id,
+                    t.quantity,
+                    t.inventory_id,
+                    t.total_cost.unwrap()
+                ));
+                for block in &entry.blocks {
+                    lines.push(format!(
+                        "Created a block of quantity ({}) at price ({})",
+                        block.quantity, block.price_per_item
+                    ));
+                }
+            }
+            TransactionType::Consume => {
+                lines.push(format!(
+                    "Transaction {} requested {} of '{}'",
+                    transaction_id, t.quantity, t.inventory_id
+                ));
+                let mut cost_of_goods = Decimal::ZERO;
+                let mut consumed = 0;
+                for block in &entry.blocks {
+                    lines.push(format!(
+                        "Consumed quantity ({}) at price ({})",
+                        block.quantity, block.price_per_item
+                    ));
+                    cost_of_goods += block.price_per_item * Decimal::from(block.quantity);
+                    consumed += block.quantity;
+                }
+                if consumed != t.quantity {
+                    lines.push(format!(
+                        "Only {} of the requested {} were consumed, a consume takes a single item",
+                        consumed, t.quantity
+                    ));
+                }
+                lines.push(format!("Cost of goods consumed: {}", cost_of_goods));
+            }
+            TransactionType::Archive => lines.push(format!(
+                "Transaction {} archived '{}'",
+                transaction_id, t.inventory_id
+            )),
+            TransactionType::Restore => lines.push(format!(
+                "Transaction {} restored '{}'",
+                transaction_id, t.inventory_id
+            )),
+        }
+
+        let stock_level = history[..=transaction_id]
+            .iter()
+            .filter(|e| e.transaction.inventory_id == t.inventory_id)
+            .fold(0, |level, e| {
+                let quantity: usize = e.blocks.iter().map(|block| block.quantity).sum();
+                match e.transaction.transaction_type {
+                    TransactionType::Produce => level + quantity,
+                    TransactionType::Consume => level - quantity,
+                    TransactionType::Archive | TransactionType::Restore => level,
+                }
+            });
+        lines.push(format!("Resulting stock level: {}", stock_level));
+
+        for document in &t.documents {
+            lines.push(format!("Referenced document {}", document));
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Retires a product. Archived products are left out of listings unless
+    /// explicitly asked for and can no longer be produced, but remaining
+    /// stock can still be consumed. Archiving is recorded in the transaction
+    /// history; to record why, transact a `TransactionType::Archive`
+    /// transaction with a document attached instead.
+    fn archive(&mut self, inventory_id: &str) -> Result<(), WarehouseError> {
+        self.transact(create_transaction(
+            inventory_id.to_string(),
+            None,
+            TransactionType::Archive,
+            0,
+        ))
+    }
+
+    /// Undoes `archive`, returning the product to the listings. Also recorded
+    /// in the transaction history.
+    fn restore(&mut self, inventory_id: &str) -> Result<(), WarehouseError> {
+        self.transact(create_transaction(
+            inventory_id.to_string(),
+            None,
+            TransactionType::Restore,
+            0,
+        ))
+    }
+
+    /// Lists known products by name, leaving out archived ones unless
+    /// `include_archived` is set.
+    fn products(&self, include_archived: bool) -> Vec<&str> {
+        let mut products: Vec<&str> = self
+            .inventory_id_map
+            .product_strings_to_ids
+            .iter()
+            .filter(|(_, key)| {
+                include_archived || !self.inventory_id_map.archived_ids.contains(key)
+            })
+            .map(|(product, _)| product.as_str())
+            .collect();
+        products.sort_unstable();
+        products
+    }
+}
+
+impl<T: MinHeap> Warehouse<T> {
+    /// Returns a builder for setting up a pre-populated warehouse without
+    /// hand-rolling a produce transaction for every opening balance.
+    ///
+    /// There is no separate policy step: the extraction policy is the heap
+    /// type parameter, e.g. `Warehouse::<InventoryHeap>::builder()`.
+    fn builder() -> WarehouseBuilder<T> {
+        WarehouseBuilder {
+            current_product: None,
+            received: None,
+            transactions: vec![],
+            missing_product: false,
+            warehouse: Warehouse::default(),
+        }
     }
 }
 
+struct WarehouseBuilder<T>
+where
+    T: MinHeap,
+{
+    current_product: Option<String>,
+    received: Option<SystemTime>,
+    transactions: Vec<Transaction>,
+    missing_product: bool,
+    warehouse: Warehouse<T>,
+}
+
+impl<T: MinHeap> WarehouseBuilder<T> {
+    /// Selects the product that subsequent calls to `opening` apply to.
+    fn product(mut self, inventory_id: &str) -> Self {
+        self.current_product = Some(inventory_id.to_string());
+        self
+    }
+
+    /// Dates the opening balances that follow as received at `received`.
+    /// Openings are dated when they are given otherwise.
+    fn at(mut self, received: SystemTime) -> Self {
+        self.received = Some(received);
+        self
+    }
+
+    /// Records an opening balance of `quantity` items costing `total_cost`
+    /// for the currently selected product.
+    fn opening(mut self, quantity: usize, total_cost: Decimal) -> Self {
+        match &self.current_product {
+            Some(inventory_id) => {
+                let mut t = create_transaction(
+                    inventory_id.clone(),
+                    Some(total_cost),
+                    TransactionType::Produce,
+                    quantity,
+                );
+                if let Some(received) = self.received {
+                    t = t.at(received);
+                }
+                self.transactions.push(t);
+            }
+            None => self.missing_product = true,
+        }
+        self
+    }
+
+    /// Replays the opening balances, in the order they were given, against
+    /// an empty warehouse.
+    fn build(mut self) -> Result<Warehouse<T>, WarehouseError> {
+        if self.missing_product {
+            println!("opening balance given before a product was selected");
+            return Err(WarehouseError);
+        }
+
+        for t in self.transactions {
+            self.warehouse.transact(t)?;
+        }
+
+        Ok(self.warehouse)
+    }
+}
 
 fn create_transaction(
     inventory_id: String,
     total_cost: Option<Decimal>,
     transaction_type: TransactionType,
-    quantity: usize
+    quantity: usize,
 ) -> Transaction {
     Transaction {
         transaction_type,
         inventory_id,
         quantity,
-        total_cost
+        total_cost,
+        documents: vec![],
+        timestamp: SystemTime::now(),
     }
 }
 
 fn main() {
-    let mut warehouse: Warehouse<InventoryHeap> = Warehouse::default();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (flags, positionals): (Vec<&str>, Vec<&str>) = args
+        .iter()
+        .map(String::as_str)
+        .partition(|arg| arg.starts_with("--"));
+    let include_archived = flags.contains(&"--include-archived");
 
-    let t = create_transaction(String::from("Acrylic Box"), Some(dec!(10.00)), TransactionType::Produce, 9);
+    // `simpletory explain <transaction-id>` explains one of the demo
+    // transactions `run` performs. History is not kept between runs, so
+    // these are the only transactions there are to explain.
+    let explain = match positionals.as_slice() {
+        [] => None,
+        ["explain", id] => match id.parse::<usize>() {
+            Ok(transaction_id) => Some(transaction_id),
+            Err(_) => usage(),
+        },
+        _ => usage(),
+    };
 
-    match warehouse.transact(t) {
-        Err(e) => panic!("Ooops {}", e),
-        _ => (),
+    // Experimental: prefer blocks that have been sitting for over 90 days,
+    // otherwise take the cheapest.
+    if flags.contains(&"--cheapest-unless-stale") {
+        run::<ScoredHeap<CheapestUnlessStale<90>>>(include_archived, explain);
+    } else {
+        run::<InventoryHeap>(include_archived, explain);
     }
+}
+
+fn usage() -> ! {
+    println!("usage: simpletory [--include-archived] [--cheapest-unless-stale] [explain <transaction-id>]");
+    println!("explain describes a transaction from this run's demo, as history is not kept between runs.");
+    println!(
+        "It does not say which reports a transaction feeds into, as there are no reports yet."
+    );
+    std::process::exit(1);
+}
 
-    let t2 = create_transaction(String::from("Acrylic Box"), None, TransactionType::Consume, 1);
+/// Runs the demo transactions, lists stocked products and, if asked,
+/// explains one of the demo transactions by its position in this run's
+/// history.
+fn run<T: MinHeap>(include_archived: bool, explain: Option<usize>) {
+    let now = SystemTime::now();
 
-    match warehouse.transact(t2) {
+    let mut warehouse: Warehouse<T> = match Warehouse::builder()
+        .product("Acrylic Box")
+        // Stock that has been sitting on the shelf for 120 days.
+        .at(now - Duration::from_secs(120 * 24 * 60 * 60))
+        .opening(3, dec!(15.00))
+        .at(now)
+        .opening(9, dec!(10.00))
+        .product("Wooden Crate")
+        .opening(2, dec!(4.00))
+        .product("Steel Drum")
+        .opening(1, dec!(30.00))
+        .build()
+    {
+        Ok(warehouse) => warehouse,
         Err(e) => panic!("Ooops {}", e),
-        _ => (),
+    };
+
+    let t = create_transaction(
+        String::from("Acrylic Box"),
+        None,
+        TransactionType::Consume,
+        1,
+    )
+    .with_document(DocumentReference::Url(String::from(
+        "https://example.com/invoices/1001.pdf",
+    )))
+    .with_document(DocumentReference::ContentHash {
+        algorithm: HashAlgorithm::Sha256,
+        digest: String::from("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"),
+    });
+
+    if let Err(e) = warehouse.transact(t) {
+        panic!("Ooops {}", e)
+    }
+
+    if let Err(e) = warehouse.archive("Wooden Crate") {
+        panic!("Ooops {}", e)
+    }
+
+    // Archived by mistake and put back.
+    if let Err(e) = warehouse
+        .archive("Steel Drum")
+        .and_then(|_| warehouse.restore("Steel Drum"))
+    {
+        panic!("Ooops {}", e)
+    }
+
+    for product in warehouse.products(include_archived) {
+        println!("Stocked product '{}'", product);
+    }
+
+    if let Some(transaction_id) = explain {
+        match warehouse.explain(transaction_id) {
+            Ok(explanation) => {
+                println!(
+                    "Explaining transaction {} of this run's demo",
+                    transaction_id
+                );
+                println!("{}", explanation)
+            }
+            // explain has already said which transaction it couldn't find.
+            Err(_) => std::process::exit(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory_heap::{InventoryHeap, MinHeap};
+    use crate::{create_transaction, DocumentReference, HashAlgorithm, TransactionType, Warehouse};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_builder_populates_opening_balances() {
+        let warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Acrylic Box")
+            .opening(9, dec!(10.00))
+            .opening(1, dec!(0.50))
+            .product("Wooden Crate")
+            .opening(2, dec!(4.00))
+            .build()
+            .unwrap();
+
+        assert_eq!(warehouse.transaction_history.history.len(), 3);
+        assert_eq!(warehouse.inventory_heaps.len(), 2);
+
+        let id = warehouse.inventory_id_map.product_strings_to_ids["Acrylic Box"];
+        let min = warehouse.inventory_heaps[&id].get_min();
+        assert_eq!(min.inventory[0].price_per_item, dec!(0.50));
+    }
+
+    #[test]
+    fn test_builder_without_product_should_fail() {
+        let warehouse = Warehouse::<InventoryHeap>::builder()
+            .opening(9, dec!(10.00))
+            .build();
+
+        assert!(warehouse.is_err());
+    }
+
+    #[test]
+    fn test_builder_with_zero_quantity_opening_should_fail() {
+        let warehouse = Warehouse::<InventoryHeap>::builder()
+            .product("Acrylic Box")
+            .opening(0, dec!(10.00))
+            .build();
+
+        assert!(warehouse.is_err());
+    }
+
+    #[test]
+    fn test_built_warehouse_accepts_transactions() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Acrylic Box")
+            .opening(1, dec!(10.00))
+            .build()
+            .unwrap();
+
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            None,
+            TransactionType::Consume,
+            1,
+        );
+        assert!(warehouse.transact(t).is_ok());
+
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            None,
+            TransactionType::Consume,
+            1,
+        );
+        assert!(warehouse.transact(t).is_err());
+    }
+
+    #[test]
+    fn test_archived_products_are_hidden_from_listings() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Acrylic Box")
+            .opening(1, dec!(10.00))
+            .product("Wooden Crate")
+            .opening(2, dec!(4.00))
+            .build()
+            .unwrap();
+
+        assert!(warehouse.archive("Wooden Crate").is_ok());
+        assert_eq!(warehouse.products(false), vec!["Acrylic Box"]);
+        assert_eq!(
+            warehouse.products(true),
+            vec!["Acrylic Box", "Wooden Crate"]
+        );
+        assert_eq!(warehouse.transaction_history.history.len(), 3);
+    }
+
+    #[test]
+    fn test_restored_products_are_listed_again() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Wooden Crate")
+            .opening(2, dec!(4.00))
+            .build()
+            .unwrap();
+
+        assert!(warehouse.restore("Wooden Crate").is_err());
+        warehouse.archive("Wooden Crate").unwrap();
+        assert!(warehouse.archive("Wooden Crate").is_err());
+        warehouse.restore("Wooden Crate").unwrap();
+
+        assert_eq!(warehouse.products(false), vec!["Wooden Crate"]);
+        assert_eq!(
+            warehouse.explain(1).unwrap(),
+            "Transaction 1 archived 'Wooden Crate'\n\
+             Resulting stock level: 2"
+        );
+        assert_eq!(
+            warehouse.explain(2).unwrap(),
+            "Transaction 2 restored 'Wooden Crate'\n\
+             Resulting stock level: 2"
+        );
+    }
+
+    #[test]
+    fn test_archive_reason_is_kept_in_history() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Wooden Crate")
+            .opening(2, dec!(4.00))
+            .build()
+            .unwrap();
+
+        let notice = DocumentReference::Url(String::from(
+            "https://example.com/notices/discontinued-wooden-crate",
+        ));
+        let t = create_transaction(
+            String::from("Wooden Crate"),
+            None,
+            TransactionType::Archive,
+            0,
+        )
+        .with_document(notice.clone());
+        warehouse.transact(t).unwrap();
+
+        assert!(warehouse.products(false).is_empty());
+        assert_eq!(
+            warehouse.transaction_history.history[1]
+                .transaction
+                .documents,
+            vec![notice]
+        );
+    }
+
+    #[test]
+    fn test_archiving_unknown_product_should_fail() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::default();
+
+        assert!(warehouse.archive("Acrylic Box").is_err());
+        assert!(warehouse.products(true).is_empty());
+    }
+
+    #[test]
+    fn test_consuming_unknown_product_does_not_list_it() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Acrylic Box")
+            .opening(1, dec!(10.00))
+            .build()
+            .unwrap();
+
+        let t = create_transaction(String::from("Typo"), None, TransactionType::Consume, 1);
+        assert!(warehouse.transact(t).is_err());
+
+        assert_eq!(warehouse.products(true), vec!["Acrylic Box"]);
+        assert!(warehouse.archive("Typo").is_err());
+    }
+
+    #[test]
+    fn test_archived_products_can_be_consumed_but_not_produced() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Wooden Crate")
+            .opening(2, dec!(4.00))
+            .build()
+            .unwrap();
+        warehouse.archive("Wooden Crate").unwrap();
+
+        let t = create_transaction(
+            String::from("Wooden Crate"),
+            None,
+            TransactionType::Consume,
+            1,
+        );
+        assert!(warehouse.transact(t).is_ok());
+
+        let t = create_transaction(
+            String::from("Wooden Crate"),
+            Some(dec!(4.00)),
+            TransactionType::Produce,
+            2,
+        );
+        assert!(warehouse.transact(t).is_err());
+    }
+
+    #[test]
+    fn test_documents_are_kept_in_history() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::default();
+
+        let invoice = DocumentReference::Url(String::from("https://example.com/invoices/1001.pdf"));
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            Some(dec!(10.00)),
+            TransactionType::Produce,
+            9,
+        )
+        .with_document(invoice.clone());
+        warehouse.transact(t).unwrap();
+
+        let photo = DocumentReference::ContentHash {
+            algorithm: HashAlgorithm::Sha256,
+            digest: String::from(
+                "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+            ),
+        };
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            None,
+            TransactionType::Consume,
+            1,
+        )
+        .with_document(photo.clone());
+        warehouse.transact(t).unwrap();
+
+        let history = &warehouse.transaction_history.history;
+        assert_eq!(history[0].transaction.documents, vec![invoice]);
+        assert_eq!(history[1].transaction.documents, vec![photo]);
     }
 
+    #[test]
+    fn test_malformed_url_should_fail() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::default();
 
+        for url in [
+            "",
+            "   ",
+            "invoice 1001",
+            "https://",
+            "ftp://example.com/invoices/1001.pdf",
+            "https://example.com/invoice 1001.pdf",
+        ] {
+            let t = create_transaction(
+                String::from("Acrylic Box"),
+                Some(dec!(10.00)),
+                TransactionType::Produce,
+                9,
+            )
+            .with_document(DocumentReference::Url(String::from(url)));
+            assert!(warehouse.transact(t).is_err());
+        }
+
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            Some(dec!(10.00)),
+            TransactionType::Produce,
+            9,
+        )
+        .with_document(DocumentReference::Url(String::from(
+            "file:///srv/evidence/damaged-box.jpg",
+        )));
+        assert!(warehouse.transact(t).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_content_hash_should_fail() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::default();
+
+        for digest in [
+            "9f86d081",
+            "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08",
+            "zf86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+        ] {
+            let t = create_transaction(
+                String::from("Acrylic Box"),
+                Some(dec!(10.00)),
+                TransactionType::Produce,
+                9,
+            )
+            .with_document(DocumentReference::ContentHash {
+                algorithm: HashAlgorithm::Sha256,
+                digest: String::from(digest),
+            });
+            assert!(warehouse.transact(t).is_err());
+        }
+
+        assert!(warehouse.transaction_history.history.is_empty());
+    }
+
+    #[test]
+    fn test_explain_produce() {
+        let warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Acrylic Box")
+            .opening(4, dec!(10.00))
+            .build()
+            .unwrap();
+
+        let explanation = warehouse.explain(0).unwrap();
+        assert_eq!(
+            explanation,
+            "Transaction 0 produced 4 of 'Acrylic Box' for a total cost of 10.00\n\
+             Created a block of quantity (4) at price (2.50)\n\
+             Resulting stock level: 4"
+        );
+    }
+
+    #[test]
+    fn test_explain_consume() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Acrylic Box")
+            .opening(4, dec!(10.00))
+            .opening(2, dec!(1.00))
+            .product("Wooden Crate")
+            .opening(2, dec!(4.00))
+            .build()
+            .unwrap();
+
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            None,
+            TransactionType::Consume,
+            1,
+        );
+        warehouse.transact(t).unwrap();
+
+        let explanation = warehouse.explain(3).unwrap();
+        assert_eq!(
+            explanation,
+            "Transaction 3 requested 1 of 'Acrylic Box'\n\
+             Consumed quantity (1) at price (0.50)\n\
+             Cost of goods consumed: 0.50\n\
+             Resulting stock level: 5"
+        );
+    }
+
+    #[test]
+    fn test_explain_consume_shows_requested_quantity() {
+        let mut warehouse: Warehouse<InventoryHeap> = Warehouse::builder()
+            .product("Acrylic Box")
+            .opening(4, dec!(10.00))
+            .build()
+            .unwrap();
+
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            None,
+            TransactionType::Consume,
+            5,
+        );
+        warehouse.transact(t).unwrap();
+
+        let explanation = warehouse.explain(1).unwrap();
+        assert_eq!(
+            explanation,
+            "Transaction 1 requested 5 of 'Acrylic Box'\n\
+             Consumed quantity (1) at price (2.50)\n\
+             Only 1 of the requested 5 were consumed, a consume takes a single item\n\
+             Cost of goods consumed: 2.50\n\
+             Resulting stock level: 3"
+        );
+    }
+
+    #[test]
+    fn test_explain_unknown_transaction_should_fail() {
+        let warehouse: Warehouse<InventoryHeap> = Warehouse::default();
+
+        assert!(warehouse.explain(0).is_err());
+    }
 }
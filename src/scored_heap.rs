@@ -0,0 +1,334 @@
+use crate::inventory_heap::{Inventory, InventoryView, MinHeap};
+use rust_decimal::prelude::*;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Scoring function for `ScoredHeap`. Blocks are extracted lowest score
+/// first.
+///
+/// `age` is the time between the produce transaction that received the
+/// block and the transaction extracting from the heap, so blocks age even
+/// while their product sees no transactions.
+pub trait BlockScore {
+    fn score(price_per_item: Decimal, age: Duration) -> Decimal;
+}
+
+/// Extracts the cheapest block unless some block has been sitting for more
+/// than `MAX_AGE_DAYS` days, in which case the oldest such block goes first.
+pub struct CheapestUnlessStale<const MAX_AGE_DAYS: u64>;
+
+impl<const MAX_AGE_DAYS: u64> BlockScore for CheapestUnlessStale<MAX_AGE_DAYS> {
+    fn score(price_per_item: Decimal, age: Duration) -> Decimal {
+        if age.as_secs() > MAX_AGE_DAYS * SECONDS_PER_DAY {
+            // Negative, so always ahead of any price, and older is lower.
+            -Decimal::from(age.as_secs())
+        } else {
+            price_per_item
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct AgedInventory {
+    inventory: Inventory,
+    received: SystemTime,
+}
+
+/// Experimental extraction policy that orders blocks by a configurable
+/// score of price and age rather than by price alone.
+///
+/// Each block keeps the time it was received. `insert_at`, `get_min_at` and
+/// `extract_at` take the time explicitly, and the plain `insert`, `get_min`
+/// and `extract` use the current time. Scores change as blocks age, so there
+/// is no heap invariant to maintain and the minimum is found with a linear
+/// scan. Ties go to the block that was inserted first.
+pub struct ScoredHeap<S: BlockScore> {
+    blocks: Vec<AgedInventory>,
+    score: PhantomData<S>,
+}
+
+impl<S: BlockScore> MinHeap for ScoredHeap<S> {
+    fn heapify(&mut self, _index: usize) {
+        // Nothing to restore, see the type level docs.
+    }
+
+    fn insert(&mut self, inventory: Inventory) {
+        self.insert_at(inventory, SystemTime::now());
+    }
+
+    fn delete(&mut self) {
+        self.delete_at(SystemTime::now());
+    }
+
+    fn extract(&mut self) -> InventoryView {
+        self.extract_at(SystemTime::now())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    fn size(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn get_min(&self) -> InventoryView {
+        self.get_min_at(SystemTime::now())
+    }
+
+    fn new() -> Self {
+        ScoredHeap {
+            blocks: vec![],
+            score: PhantomData,
+        }
+    }
+
+    fn insert_at(&mut self, inventory: Inventory, received: SystemTime) {
+        self.blocks.push(AgedInventory {
+            inventory,
+            received,
+        });
+    }
+
+    fn get_min_at(&self, now: SystemTime) -> InventoryView {
+        InventoryView {
+            inventory: vec![self.blocks[self.min_index(now)].inventory],
+        }
+    }
+
+    fn extract_at(&mut self, now: SystemTime) -> InventoryView {
+        let min = self.get_min_at(now);
+        self.delete_at(now);
+        min
+    }
+}
+
+impl<S: BlockScore> ScoredHeap<S> {
+    fn delete_at(&mut self, now: SystemTime) {
+        // Nothing to do, heap is empty. Results in a no-op.
+        if self.blocks.is_empty() {
+            return;
+        }
+
+        let index = self.min_index(now);
+        if self.blocks[index].inventory.quantity > 1 {
+            self.blocks[index].inventory.quantity -= 1;
+        } else {
+            self.blocks.remove(index);
+        }
+    }
+
+    fn min_index(&self, now: SystemTime) -> usize {
+        let mut smallest = 0;
+        let mut smallest_score = self.score_at(0, now);
+
+        for index in 1..self.size() {
+            let score = self.score_at(index, now);
+            if score < smallest_score {
+                smallest = index;
+                smallest_score = score;
+            }
+        }
+
+        smallest
+    }
+
+    fn score_at(&self, index: usize, now: SystemTime) -> Decimal {
+        let block = &self.blocks[index];
+        // A block received after `now` (out of order timestamps) counts as
+        // brand new.
+        let age = now.duration_since(block.received).unwrap_or(Duration::ZERO);
+        S::score(block.inventory.price_per_item, age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::equivalence::assert_equivalent;
+    use crate::inventory_heap::{Inventory, InventoryHeap, MinHeap};
+    use crate::scored_heap::{BlockScore, CheapestUnlessStale, ScoredHeap, SECONDS_PER_DAY};
+    use crate::{create_transaction, TransactionType, Warehouse};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::time::{Duration, SystemTime};
+
+    struct Cheapest;
+
+    impl BlockScore for Cheapest {
+        fn score(price_per_item: Decimal, _age: Duration) -> Decimal {
+            price_per_item
+        }
+    }
+
+    fn day(day: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(day * SECONDS_PER_DAY)
+    }
+
+    fn block(price_per_item: Decimal, quantity: usize) -> Inventory {
+        Inventory {
+            price_per_item,
+            quantity,
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_empty_heap_should_panic() {
+        let heap = ScoredHeap::<Cheapest>::new();
+        heap.get_min();
+    }
+
+    #[test]
+    fn test_fresh_blocks_are_extracted_cheapest_first() {
+        let mut heap = ScoredHeap::<CheapestUnlessStale<90>>::new();
+
+        heap.insert(block(dec!(3), 1));
+        heap.insert(block(dec!(1), 1));
+        heap.insert(block(dec!(2), 1));
+
+        assert_eq!(heap.extract().inventory[0].price_per_item, dec!(1));
+        assert_eq!(heap.extract().inventory[0].price_per_item, dec!(2));
+        assert_eq!(heap.extract().inventory[0].price_per_item, dec!(3));
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_stale_block_is_extracted_before_cheaper_ones() {
+        let mut heap = ScoredHeap::<CheapestUnlessStale<90>>::new();
+
+        heap.insert_at(block(dec!(5), 1), day(0));
+        heap.insert_at(block(dec!(1), 10), day(50));
+
+        assert_eq!(
+            heap.extract_at(day(90)).inventory[0].price_per_item,
+            dec!(1)
+        );
+
+        // The expensive block is now over 90 days old.
+        assert_eq!(
+            heap.get_min_at(day(91)).inventory[0].price_per_item,
+            dec!(5)
+        );
+        assert_eq!(
+            heap.extract_at(day(91)).inventory[0].price_per_item,
+            dec!(5)
+        );
+        assert_eq!(
+            heap.extract_at(day(91)).inventory[0].price_per_item,
+            dec!(1)
+        );
+    }
+
+    #[test]
+    fn test_oldest_stale_block_goes_first() {
+        let mut heap = ScoredHeap::<CheapestUnlessStale<90>>::new();
+
+        heap.insert_at(block(dec!(5), 1), day(0));
+        heap.insert_at(block(dec!(4), 1), day(1));
+        heap.insert_at(block(dec!(1), 1), day(1));
+
+        assert_eq!(
+            heap.extract_at(day(200)).inventory[0].price_per_item,
+            dec!(5)
+        );
+        assert_eq!(
+            heap.extract_at(day(200)).inventory[0].price_per_item,
+            dec!(4)
+        );
+    }
+
+    #[test]
+    fn test_blocks_inserted_now_are_fresh() {
+        let mut heap = ScoredHeap::<CheapestUnlessStale<90>>::new();
+
+        heap.insert(block(dec!(5), 1));
+        heap.insert(block(dec!(1), 1));
+
+        let now = SystemTime::now();
+        assert_eq!(heap.extract_at(now).inventory[0].price_per_item, dec!(1));
+
+        let later = now + Duration::from_secs(91 * SECONDS_PER_DAY);
+        heap.insert_at(block(dec!(1), 1), later);
+        assert_eq!(heap.extract_at(later).inventory[0].price_per_item, dec!(5));
+    }
+
+    #[test]
+    fn test_blocks_of_idle_products_still_go_stale() {
+        let mut warehouse: Warehouse<ScoredHeap<CheapestUnlessStale<90>>> = Warehouse::default();
+
+        for (price, received) in [(dec!(5), day(0)), (dec!(1), day(10))] {
+            let t = create_transaction(
+                String::from("Acrylic Box"),
+                Some(price),
+                TransactionType::Produce,
+                1,
+            )
+            .at(received);
+            warehouse.transact(t).unwrap();
+        }
+
+        // No transactions touch the product for 100 days.
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            None,
+            TransactionType::Consume,
+            1,
+        )
+        .at(day(100));
+        warehouse.transact(t).unwrap();
+
+        let consumed = &warehouse.transaction_history.history[2].blocks;
+        assert_eq!(consumed[0].price_per_item, dec!(5));
+    }
+
+    #[test]
+    fn test_dated_opening_balances_go_stale() {
+        let mut warehouse: Warehouse<ScoredHeap<CheapestUnlessStale<90>>> = Warehouse::builder()
+            .product("Acrylic Box")
+            .at(day(0))
+            .opening(1, dec!(5))
+            .at(day(95))
+            .opening(1, dec!(1))
+            .build()
+            .unwrap();
+
+        let t = create_transaction(
+            String::from("Acrylic Box"),
+            None,
+            TransactionType::Consume,
+            1,
+        )
+        .at(day(100));
+        warehouse.transact(t).unwrap();
+
+        let consumed = &warehouse.transaction_history.history[2].blocks;
+        assert_eq!(consumed[0].price_per_item, dec!(5));
+    }
+
+    #[test]
+    fn test_price_only_score_matches_inventory_heap() {
+        let transactions: Vec<_> = (1..20)
+            .map(|i| {
+                if i % 3 == 0 {
+                    create_transaction(
+                        String::from("Acrylic Box"),
+                        None,
+                        TransactionType::Consume,
+                        1,
+                    )
+                } else {
+                    create_transaction(
+                        String::from("Acrylic Box"),
+                        Some(Decimal::new((i * 7) % 19 + 1, 0)),
+                        TransactionType::Produce,
+                        2,
+                    )
+                }
+            })
+            .collect();
+
+        assert_equivalent::<InventoryHeap, ScoredHeap<Cheapest>>(&transactions);
+    }
+}